@@ -1,17 +1,21 @@
 use anyhow::{Context, Result};
 use byteorder::{BigEndian, WriteBytesExt};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use ffmpeg_next as ffmpeg;
+use ffmpeg::ffi;
 use ffmpeg::format::Pixel;
 use ffmpeg::software::scaling::{context::Context as Scaler, flag::Flags};
 use ffmpeg::util::dictionary::Owned as Dictionary;
 use image::{ImageBuffer, Rgba};
 use rusb::{DeviceHandle, GlobalContext};
+use std::ffi::CString;
+use std::fmt;
 use std::fs;
 use std::io::Write;
 use std::process::Command;
+use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::Duration;
 
 // Config Settings
@@ -33,6 +37,35 @@ const MAX_PAYLOAD_VIDEO: usize = 501;
 const PKT_SIZE_VIDEO: usize = 512;
 const HEADER_SIZE: usize = 11;
 
+// Producer/consumer ring buffer sizing (must be powers of two)
+const FILE_RING_CAPACITY: usize = 64;
+const LIVE_RING_CAPACITY: usize = 8;
+
+// Encoder Selection ---
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum EncoderChoice {
+    /// Prefer hardware encoders, falling back to libx264
+    Auto,
+    /// VAAPI (Intel/AMD GPU)
+    Vaapi,
+    /// V4L2 M2M stateful encoder (ARM SoCs)
+    V4l2m2m,
+    /// Software libx264
+    X264,
+}
+
+impl fmt::Display for EncoderChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EncoderChoice::Auto => "auto",
+            EncoderChoice::Vaapi => "vaapi",
+            EncoderChoice::V4l2m2m => "v4l2m2m",
+            EncoderChoice::X264 => "x264",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 // Clap Cli Options ---
 #[derive(Parser)]
 #[command(name = "galahad2lcd")]
@@ -52,6 +85,14 @@ enum Commands {
         /// Rotation in degrees (0, 90, 180, 270)
         #[arg(short, long, default_value_t = 0)]
         rotate: i32,
+
+        /// Video encoder backend to use
+        #[arg(long, value_enum, default_value_t = EncoderChoice::Auto)]
+        encoder: EncoderChoice,
+
+        /// VAAPI render node used when --encoder=vaapi (or auto-detected)
+        #[arg(long, default_value = "/dev/dri/renderD128")]
+        vaapi_device: String,
     },
 
     /// Updates the /etc/default/galahad2lcd config and restarts the service
@@ -63,6 +104,37 @@ enum Commands {
         /// Rotation in degrees (0, 90, 180, 270)
         #[arg(short, long, default_value_t = 0)]
         rotate: i32,
+
+        /// Video encoder backend to use
+        #[arg(long, value_enum, default_value_t = EncoderChoice::Auto)]
+        encoder: EncoderChoice,
+
+        /// VAAPI render node used when --encoder=vaapi (or auto-detected)
+        #[arg(long, default_value = "/dev/dri/renderD128")]
+        vaapi_device: String,
+    },
+
+    /// Stream a live V4L2 capture device (webcam/capture card) straight to the LCD
+    Live {
+        /// Path to the V4L2 device (e.g. /dev/video0)
+        #[arg(short, long, default_value = "/dev/video0")]
+        device: String,
+
+        /// Requested capture width
+        #[arg(long, default_value_t = 640)]
+        width: u32,
+
+        /// Requested capture height
+        #[arg(long, default_value_t = 480)]
+        height: u32,
+
+        /// Requested capture framerate
+        #[arg(long, default_value_t = 30)]
+        framerate: u32,
+
+        /// Rotation in degrees (0, 90, 180, 270)
+        #[arg(short, long, default_value_t = 0)]
+        rotate: i32,
     },
 }
 
@@ -70,21 +142,25 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Daemon { input, rotate } => {
-            run_daemon(input, rotate)
+        Commands::Daemon { input, rotate, encoder, vaapi_device } => {
+            run_daemon(input, rotate, encoder, vaapi_device)
+        }
+
+        Commands::Live { device, width, height, framerate, rotate } => {
+            run_live_daemon(device, width, height, framerate, rotate)
         }
-        
-        Commands::SetArgs { input, rotate } => {
+
+        Commands::SetArgs { input, rotate, encoder, vaapi_device } => {
             println!("[+] Updating configuration...");
-            
+
             let abs_path = std::fs::canonicalize(&input)
                 .with_context(|| format!("[-] Could not find file: {}", input))?;
             let abs_path_str = abs_path.to_string_lossy();
 
             // Format the content for /etc/default/galahad2lcd
             let config_content = format!(
-                "MYAPP_ARGS=\"--input {} --rotate {}\"", 
-                abs_path_str, rotate
+                "MYAPP_ARGS=\"--input {} --rotate {} --encoder {} --vaapi-device {}\"",
+                abs_path_str, rotate, encoder, vaapi_device
             );
 
             if let Err(e) = fs::write(CONFIG_PATH, config_content) {
@@ -112,105 +188,627 @@ fn main() -> Result<()> {
     }
 }
 
-fn run_daemon(input_path: String, rotation: i32) -> Result<()> {
+/// Fixed-capacity ring of encoded H.264 access units shared between a
+/// producer thread (decode+encode, or a live capture source) and the
+/// consumer thread that paces playback and writes to the USB device.
+/// `capacity` must be a power of two so the index mask trick works.
+struct PacketRing {
+    state: Mutex<RingState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    mask: usize,
+    drop_when_full: bool,
+}
+
+struct RingState {
+    slots: Vec<Vec<u8>>,
+    ri: usize,
+    wi: usize,
+    closed: bool,
+}
+
+impl PacketRing {
+    /// `drop_when_full` trades buffering for latency: live sources drop the
+    /// oldest queued frame instead of blocking the producer, finite files
+    /// block so no frame is lost.
+    fn new(capacity: usize, drop_when_full: bool) -> Self {
+        assert!(capacity.is_power_of_two(), "ring capacity must be a power of two");
+        Self {
+            state: Mutex::new(RingState {
+                slots: vec![Vec::new(); capacity],
+                ri: 0,
+                wi: 0,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            mask: capacity - 1,
+            drop_when_full,
+        }
+    }
+
+    /// Number of queued, undequeued packets. `ri`/`wi` grow monotonically
+    /// (only their masked value is used to index into `slots`), so their
+    /// plain difference is the count — masking it too would alias "full"
+    /// with "empty" whenever `wi - ri` lands on a multiple of `capacity`.
+    fn len(state: &RingState) -> usize {
+        state.wi.wrapping_sub(state.ri)
+    }
+
+    fn enqueue(&self, packet: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return;
+            }
+            if Self::len(&state) < self.capacity {
+                break;
+            }
+            if self.drop_when_full {
+                state.ri = state.ri.wrapping_add(1);
+                break;
+            }
+            state = self.not_full.wait(state).unwrap();
+        }
+
+        let idx = state.wi & self.mask;
+        state.slots[idx] = packet;
+        state.wi = state.wi.wrapping_add(1);
+        drop(state);
+        self.not_empty.notify_one();
+    }
+
+    /// Blocks until a packet is available, or returns `None` once the ring
+    /// has been closed and fully drained.
+    fn dequeue(&self) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if Self::len(&state) > 0 {
+                let idx = state.ri & self.mask;
+                let packet = std::mem::take(&mut state.slots[idx]);
+                state.ri = state.ri.wrapping_add(1);
+                drop(state);
+                self.not_full.notify_one();
+                return Some(packet);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.not_empty.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+/// Consumer side of the ring: paces dequeued packets at `fps` and writes
+/// them to the USB device. Shared by the file daemon and live capture.
+fn stream_ring_packets(
+    ring: &PacketRing,
+    handle: &mut DeviceHandle<GlobalContext>,
+    running: Arc<AtomicBool>,
+    fps: f64,
+) -> Result<()> {
+    let safe_fps = if fps <= 0.0 || fps > 120.0 { 30.0 } else { fps };
+    let target_frame_time = Duration::from_secs_f64(1.0 / safe_fps);
+
+    println!("[+] Streaming from ring buffer at {:.2} FPS (Interval: {:?})", safe_fps, target_frame_time);
+
+    while running.load(Ordering::SeqCst) {
+        let frame_data = match ring.dequeue() {
+            Some(data) => data,
+            None => break,
+        };
+
+        let start = std::time::Instant::now();
+
+        if let Err(e) = send_packet_to_usb(handle, &frame_data) {
+            eprintln!("[-] USB Error: {:?}", e);
+        }
+
+        let elapsed = start.elapsed();
+        if target_frame_time > elapsed {
+            std::thread::sleep(target_frame_time - elapsed);
+        }
+    }
+    Ok(())
+}
+
+fn run_daemon(input_path: String, rotation: i32, encoder: EncoderChoice, vaapi_device: String) -> Result<()> {
     let running = Arc::new(AtomicBool::new(true));
+    let ring = Arc::new(PacketRing::new(FILE_RING_CAPACITY, false));
+
     let r = running.clone();
+    let ring_for_ctrlc = ring.clone();
     ctrlc::set_handler(move || {
         r.store(false, Ordering::SeqCst);
+        ring_for_ctrlc.close();
         println!("[!] Stopping driver...");
     })?;
 
     ffmpeg::init()?;
 
-    let cached_file = "/tmp/galahad_cache.h264";
-    
-    println!("[!] Transcoding input to H.264 (Rotation: {}Â°)...", rotation);
-    let playback_fps = transcode_to_h264(&input_path, cached_file, rotation)?;
-    println!("[+] Video FPS Detected: {:.2}", playback_fps);
-
-    println!("[!] Pre-load H.264 packets into RAM...");
-    let video_packets = preload_packets(cached_file)?;
-    println!("[!] Buffered {} frames", video_packets.len());
-
     println!("[!] Connecting to Lian Li device...");
     let mut handle = open_device(VENDOR_ID, PRODUCT_ID)?;
     prepare_usb_device(&mut handle)?;
 
-    stream_buffered_packets(&video_packets, &mut handle, running, playback_fps)?;
+    let (fps_tx, fps_rx) = std::sync::mpsc::channel();
+    let producer_running = running.clone();
+    let producer_ring = ring.clone();
+    let use_annexb_fast_path = probe_annexb_480_baseline(&input_path)?;
+    let producer = std::thread::spawn(move || {
+        let result = if use_annexb_fast_path {
+            run_annexb_producer(&input_path, &producer_ring, &producer_running, fps_tx)
+        } else {
+            run_file_producer(
+                &input_path, rotation, encoder, &vaapi_device, &producer_ring, &producer_running, fps_tx,
+            )
+        };
+        if let Err(e) = result {
+            eprintln!("[-] Producer error: {:?}", e);
+        }
+        producer_ring.close();
+    });
+
+    let playback_fps = fps_rx.recv().context("[-] Producer exited before reporting its FPS")?;
+    println!("[+] Video FPS Detected: {:.2}", playback_fps);
+
+    stream_ring_packets(&ring, &mut handle, running.clone(), playback_fps)?;
+
+    running.store(false, Ordering::SeqCst);
+    ring.close();
+    let _ = producer.join();
 
     Ok(())
 }
 
-fn preload_packets(path: &str) -> Result<Vec<Vec<u8>>> {
-    let mut ictx = ffmpeg::format::input(&path)?;
-    let input_stream = ictx.streams().best(ffmpeg::media::Type::Video)
-        .ok_or(anyhow::anyhow!("[-] No video stream found in file"))?;
-    let stream_index = input_stream.index();
+/// Extracts a playback FPS from a demuxed stream, falling back to 30 when
+/// the container doesn't report one (e.g. a raw elementary stream).
+fn detect_stream_fps(input_stream: &ffmpeg::format::stream::Stream) -> f64 {
+    let fps_rational = input_stream.avg_frame_rate();
+    if fps_rational.denominator() == 0 {
+        let r_fps = input_stream.rate();
+        if r_fps.denominator() == 0 { 30.0 } else { r_fps.numerator() as f64 / r_fps.denominator() as f64 }
+    } else {
+        fps_rational.numerator() as f64 / fps_rational.denominator() as f64
+    }
+}
+
+/// Cheaply checks whether `input_path` is already a 480x480 baseline H.264
+/// Annex-B elementary stream, in which case `run_annexb_producer` can send
+/// its NAL units straight to the device with no transcode at all.
+///
+/// This is a probe, not a validator: any open/parse failure just means the
+/// file isn't eligible for the fast path, so it returns `Ok(false)` rather
+/// than propagating the error and aborting `run_daemon`. It never returns
+/// `Err`.
+fn probe_annexb_480_baseline(input_path: &str) -> Result<bool> {
+    let is_raw_h264_ext = matches!(
+        std::path::Path::new(input_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref(),
+        Some("h264") | Some("264")
+    );
+    if !is_raw_h264_ext {
+        return Ok(false);
+    }
 
-    let mut buffered_packets = Vec::new();
+    let probe_result: Result<bool> = (|| {
+        let mut ictx = ffmpeg::format::input(&input_path)?;
+        let input_stream = match ictx.streams().best(ffmpeg::media::Type::Video) {
+            Some(stream) => stream,
+            None => return Ok(false),
+        };
 
-    for (stream, packet) in ictx.packets() {
-        if stream.index() == stream_index {
-            if let Some(data) = packet.data() {
-                buffered_packets.push(data.to_vec());
+        if input_stream.parameters().id() != ffmpeg::codec::Id::H264 {
+            return Ok(false);
+        }
+
+        let decoder_ctx = ffmpeg::codec::context::Context::from_parameters(input_stream.parameters())?;
+        let decoder = decoder_ctx.decoder().video()?;
+
+        let is_target_resolution = decoder.width() == SCREEN_WIDTH && decoder.height() == SCREEN_HEIGHT;
+        let is_baseline = matches!(
+            decoder.profile(),
+            ffmpeg::codec::Profile::H264(ffmpeg::codec::profile::H264::Baseline)
+                | ffmpeg::codec::Profile::H264(ffmpeg::codec::profile::H264::ConstrainedBaseline)
+        );
+
+        Ok(is_target_resolution && is_baseline)
+    })();
+
+    Ok(probe_result.unwrap_or(false))
+}
+
+/// One H.264 NAL unit carved out of an Annex-B byte stream: its type (the
+/// low 5 bits of the first byte after the start code) and the full byte
+/// range of the NAL, start code included, so it can be forwarded as-is.
+struct AnnexBNal<'a> {
+    nal_type: u8,
+    bytes: &'a [u8],
+}
+
+/// Walks an Annex-B byte stream looking for `00 00 01` / `00 00 00 01`
+/// start codes and slices out the NAL unit that follows each one.
+fn scan_annexb_nals(data: &[u8]) -> Vec<AnnexBNal<'_>> {
+    let mut start_codes = Vec::new();
+    let mut i = 0;
+
+    while i + 2 < data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            start_codes.push((i, 3));
+            i += 3;
+        } else if i + 3 < data.len() && data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 0 && data[i + 3] == 1 {
+            start_codes.push((i, 4));
+            i += 4;
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut nals = Vec::with_capacity(start_codes.len());
+    for (idx, &(nal_begin, code_len)) in start_codes.iter().enumerate() {
+        let payload_begin = nal_begin + code_len;
+        if payload_begin >= data.len() {
+            continue;
+        }
+        let nal_end = start_codes.get(idx + 1).map(|&(next_begin, _)| next_begin).unwrap_or(data.len());
+        nals.push(AnnexBNal {
+            nal_type: data[payload_begin] & 0x1F,
+            bytes: &data[nal_begin..nal_end],
+        });
+    }
+
+    nals
+}
+
+/// Groups Annex-B NAL units into access units, one per decodable frame. A
+/// new access unit starts as soon as the first NAL (VCL or leading
+/// SPS(7)/PPS(8)/SEI(6)) arrives after a previous VCL NAL, so those leading
+/// non-VCL NALs stay attached to the slice/IDR that follows them rather than
+/// trailing the picture before them.
+fn group_access_units(nals: &[AnnexBNal<'_>]) -> Vec<Vec<u8>> {
+    let mut access_units = Vec::new();
+    let mut current = Vec::new();
+    let mut current_has_vcl = false;
+
+    for nal in nals {
+        let is_vcl = (1..=5).contains(&nal.nal_type);
+
+        if current_has_vcl {
+            access_units.push(std::mem::take(&mut current));
+            current_has_vcl = false;
+        }
+
+        current.extend_from_slice(nal.bytes);
+
+        if is_vcl {
+            current_has_vcl = true;
+        }
+    }
+
+    if !current.is_empty() {
+        access_units.push(current);
+    }
+
+    access_units
+}
+
+/// Splits a pre-encoded 480x480 baseline Annex-B file into access units and
+/// pushes them straight into `ring`, with no decode/encode step at all.
+fn run_annexb_producer(
+    input_path: &str,
+    ring: &PacketRing,
+    running: &AtomicBool,
+    fps_tx: std::sync::mpsc::Sender<f64>,
+) -> Result<()> {
+    println!("[!] Pre-encoded 480x480 baseline H.264 detected, skipping transcode...");
+
+    let raw = fs::read(input_path).with_context(|| format!("[-] Could not read {}", input_path))?;
+    let nals = scan_annexb_nals(&raw);
+    let access_units = group_access_units(&nals);
+
+    if access_units.is_empty() {
+        return Err(anyhow::anyhow!("[-] No NAL units found in {}", input_path));
+    }
+    println!("[+] Split into {} access units", access_units.len());
+
+    let fps = {
+        let mut ictx = ffmpeg::format::input(&input_path)?;
+        let input_stream = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| anyhow::anyhow!("[-] No video stream found in {}", input_path))?;
+        detect_stream_fps(&input_stream)
+    };
+    let _ = fps_tx.send(fps);
+
+    while running.load(Ordering::SeqCst) {
+        for access_unit in &access_units {
+            if !running.load(Ordering::SeqCst) {
+                break;
             }
+            ring.enqueue(access_unit.clone());
         }
     }
 
-    Ok(buffered_packets)
+    Ok(())
 }
 
-fn stream_buffered_packets(
-    packets: &[Vec<u8>],
-    handle: &mut DeviceHandle<GlobalContext>,
-    running: Arc<AtomicBool>,
-    fps: f64,
+/// Decodes and transcodes `input_path` frame by frame, pushing each encoded
+/// access unit into `ring`. Restarts the decoder at EOF so finite files keep
+/// looping forever, matching the old preload-and-replay behavior.
+fn run_file_producer(
+    input_path: &str,
+    rotation: i32,
+    encoder: EncoderChoice,
+    vaapi_device: &str,
+    ring: &PacketRing,
+    running: &AtomicBool,
+    fps_tx: std::sync::mpsc::Sender<f64>,
 ) -> Result<()> {
-    let safe_fps = if fps <= 0.0 || fps > 120.0 { 30.0 } else { fps };
-    let target_frame_time = Duration::from_secs_f64(1.0 / safe_fps);
-    
-    println!("[+] Streaming from RAM at {:.2} FPS (Interval: {:?})", safe_fps, target_frame_time);
+    println!("[!] Transcoding input to H.264 (Rotation: {}Â°)...", rotation);
+
+    let mut fps_reported = false;
 
     while running.load(Ordering::SeqCst) {
-        for frame_data in packets {
-            if !running.load(Ordering::SeqCst) { break; }
+        let mut ictx = ffmpeg::format::input(&input_path)?;
+        let input_stream = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| anyhow::anyhow!("[-] No video stream found in {}", input_path))?;
+        let video_stream_index = input_stream.index();
+
+        let fps = detect_stream_fps(&input_stream);
+
+        let decoder_ctx = ffmpeg::codec::context::Context::from_parameters(input_stream.parameters())?;
+        let mut decoder = decoder_ctx.decoder().video()?;
+        let mut transcoder = FrameTranscoder::new(fps, encoder, vaapi_device)?;
+
+        if !fps_reported {
+            let _ = fps_tx.send(fps);
+            fps_reported = true;
+        }
 
-            let start = std::time::Instant::now();
+        for (stream, pkt) in ictx.packets() {
+            if !running.load(Ordering::SeqCst) {
+                break;
+            }
+            if stream.index() != video_stream_index {
+                continue;
+            }
+
+            decoder.send_packet(&pkt)?;
 
-            if let Err(e) = send_packet_to_usb(handle, frame_data) {
-                eprintln!("[-] USB Error: {:?}", e);
+            let mut decoded_frame = ffmpeg::util::frame::Video::empty();
+            while decoder.receive_frame(&mut decoded_frame).is_ok() {
+                for packet in transcoder.encode_frame(&decoded_frame, rotation)? {
+                    if let Some(data) = packet.data() {
+                        ring.enqueue(data.to_vec());
+                    }
+                }
             }
+        }
 
-            let elapsed = start.elapsed();
-            if target_frame_time > elapsed {
-                std::thread::sleep(target_frame_time - elapsed);
+        for packet in transcoder.flush()? {
+            if let Some(data) = packet.data() {
+                ring.enqueue(data.to_vec());
             }
         }
     }
+
     Ok(())
 }
 
-fn transcode_to_h264(input_path: &str, output_path: &str, rotation: i32) -> Result<f64> {
-    let mut ictx = ffmpeg::format::input(&input_path)?;
+fn run_live_daemon(device: String, width: u32, height: u32, framerate: u32, rotation: i32) -> Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let ring = Arc::new(PacketRing::new(LIVE_RING_CAPACITY, true));
+
+    let r = running.clone();
+    let ring_for_ctrlc = ring.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+        ring_for_ctrlc.close();
+        println!("[!] Stopping driver...");
+    })?;
+
+    ffmpeg::init()?;
+
+    println!("[!] Connecting to Lian Li device...");
+    let mut handle = open_device(VENDOR_ID, PRODUCT_ID)?;
+    prepare_usb_device(&mut handle)?;
+
+    let fps = if framerate == 0 { 30.0 } else { framerate as f64 };
+
+    let producer_running = running.clone();
+    let producer_ring = ring.clone();
+    let producer = std::thread::spawn(move || {
+        if let Err(e) = run_live_producer(&device, width, height, framerate, rotation, &producer_ring, &producer_running) {
+            eprintln!("[-] Live capture producer error: {:?}", e);
+        }
+        producer_ring.close();
+    });
+
+    println!("[+] Streaming live capture at {:.2} FPS", fps);
+    stream_ring_packets(&ring, &mut handle, running.clone(), fps)?;
+
+    running.store(false, Ordering::SeqCst);
+    ring.close();
+    let _ = producer.join();
+
+    Ok(())
+}
+
+/// Captures from a V4L2 device, encodes each frame, and pushes the result
+/// into `ring`. When the ring is full the oldest queued frame is dropped
+/// instead of blocking the capture (see `PacketRing::new`), since a live
+/// feed can't be paused while the consumer catches up.
+fn run_live_producer(
+    device: &str,
+    width: u32,
+    height: u32,
+    framerate: u32,
+    rotation: i32,
+    ring: &PacketRing,
+    running: &AtomicBool,
+) -> Result<()> {
+    println!("[!] Opening capture device {} ({}x{} @ {}fps)...", device, width, height, framerate);
+    let format = ffmpeg::format::find_input_format("v4l2").ok_or_else(|| {
+        anyhow::anyhow!("[-] v4l2 input format not available in this ffmpeg build")
+    })?;
+
+    let mut capture_opts = Dictionary::new();
+    capture_opts.set("video_size", &format!("{}x{}", width, height));
+    capture_opts.set("framerate", &framerate.to_string());
+    capture_opts.set("input_format", "yuyv422");
+
+    let mut ictx = ffmpeg::format::input_with_dictionary_format(&device, format, capture_opts)?;
     let input_stream = ictx
         .streams()
         .best(ffmpeg::media::Type::Video)
-        .ok_or(anyhow::anyhow!("No video stream found"))?;
-    
+        .ok_or_else(|| anyhow::anyhow!("[-] No video stream found on {}", device))?;
     let video_stream_index = input_stream.index();
 
-    let fps_rational = input_stream.avg_frame_rate();
-    let fps = if fps_rational.denominator() == 0 {
-        let r_fps = input_stream.rate();
-        if r_fps.denominator() == 0 { 30.0 } else { r_fps.numerator() as f64 / r_fps.denominator() as f64 }
-    } else {
-        fps_rational.numerator() as f64 / fps_rational.denominator() as f64
-    };
-
     let decoder_ctx = ffmpeg::codec::context::Context::from_parameters(input_stream.parameters())?;
     let mut decoder = decoder_ctx.decoder().video()?;
 
-    let mut octx = ffmpeg::format::output(&output_path)?;
+    let fps = if framerate == 0 { 30.0 } else { framerate as f64 };
+    let mut transcoder = FrameTranscoder::new(fps, EncoderChoice::Auto, "/dev/dri/renderD128")?;
+
+    'capture: while running.load(Ordering::SeqCst) {
+        for (stream, pkt) in ictx.packets() {
+            if !running.load(Ordering::SeqCst) {
+                break 'capture;
+            }
+
+            if stream.index() != video_stream_index {
+                continue;
+            }
+
+            decoder.send_packet(&pkt)?;
+
+            let mut decoded_frame = ffmpeg::util::frame::Video::empty();
+            while decoder.receive_frame(&mut decoded_frame).is_ok() {
+                for packet in transcoder.encode_frame(&decoded_frame, rotation)? {
+                    if let Some(data) = packet.data() {
+                        ring.enqueue(data.to_vec());
+                    }
+                }
+            }
+        }
+    }
+
+    for packet in transcoder.flush()? {
+        if let Some(data) = packet.data() {
+            ring.enqueue(data.to_vec());
+        }
+    }
+
+    Ok(())
+}
+
+/// Which concrete encoder backend a `FrameTranscoder` ended up opening.
+/// VAAPI needs the hardware frames context kept alive for the lifetime
+/// of the encoder, so it carries one along.
+enum EncoderBackend {
+    X264,
+    Vaapi(VaapiContext),
+    V4l2m2m,
+}
+
+/// Owns the VAAPI hw device + hw frames contexts and uploads software
+/// NV12 frames into hardware surfaces for `h264_vaapi`.
+struct VaapiContext {
+    hw_device_ctx: *mut ffi::AVBufferRef,
+    hw_frames_ctx: *mut ffi::AVBufferRef,
+}
+
+impl VaapiContext {
+    fn new(device_path: &str, width: u32, height: u32) -> Result<Self> {
+        unsafe {
+            let mut hw_device_ctx: *mut ffi::AVBufferRef = ptr::null_mut();
+            let device_cstr = CString::new(device_path)?;
+            let ret = ffi::av_hwdevice_ctx_create(
+                &mut hw_device_ctx,
+                ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+                device_cstr.as_ptr(),
+                ptr::null_mut(),
+                0,
+            );
+            if ret < 0 {
+                return Err(anyhow::anyhow!("[-] Failed to open VAAPI device {}", device_path));
+            }
+
+            let hw_frames_ctx = ffi::av_hwframe_ctx_alloc(hw_device_ctx);
+            if hw_frames_ctx.is_null() {
+                ffi::av_buffer_unref(&mut hw_device_ctx);
+                return Err(anyhow::anyhow!("[-] Failed to allocate VAAPI frames context"));
+            }
+
+            let frames_ctx = (*hw_frames_ctx).data as *mut ffi::AVHWFramesContext;
+            (*frames_ctx).format = ffi::AVPixelFormat::AV_PIX_FMT_VAAPI;
+            (*frames_ctx).sw_format = ffi::AVPixelFormat::AV_PIX_FMT_NV12;
+            (*frames_ctx).width = width as i32;
+            (*frames_ctx).height = height as i32;
+            (*frames_ctx).initial_pool_size = 4;
+
+            let ret = ffi::av_hwframe_ctx_init(hw_frames_ctx);
+            if ret < 0 {
+                ffi::av_buffer_unref(&mut { hw_frames_ctx });
+                ffi::av_buffer_unref(&mut hw_device_ctx);
+                return Err(anyhow::anyhow!("[-] Failed to initialize VAAPI frames context"));
+            }
+
+            Ok(Self { hw_device_ctx, hw_frames_ctx })
+        }
+    }
+
+    /// Upload a software NV12 frame into a VAAPI surface the encoder can consume.
+    fn upload(&self, sw_frame: &ffmpeg::util::frame::Video) -> Result<ffmpeg::util::frame::Video> {
+        unsafe {
+            let hw_frame_ptr = ffi::av_frame_alloc();
+            if hw_frame_ptr.is_null() {
+                return Err(anyhow::anyhow!("[-] Failed to allocate VAAPI frame"));
+            }
+
+            let ret = ffi::av_hwframe_get_buffer(self.hw_frames_ctx, hw_frame_ptr, 0);
+            if ret < 0 {
+                ffi::av_frame_free(&mut { hw_frame_ptr });
+                return Err(anyhow::anyhow!("[-] Failed to get a VAAPI surface from the pool"));
+            }
+
+            let ret = ffi::av_hwframe_transfer_data(hw_frame_ptr, sw_frame.as_ptr(), 0);
+            if ret < 0 {
+                ffi::av_frame_free(&mut { hw_frame_ptr });
+                return Err(anyhow::anyhow!("[-] Failed to upload frame to VAAPI surface"));
+            }
+
+            (*hw_frame_ptr).pts = sw_frame.pts().unwrap_or(0);
+
+            Ok(ffmpeg::util::frame::Video::wrap(hw_frame_ptr))
+        }
+    }
+}
+
+impl Drop for VaapiContext {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::av_buffer_unref(&mut self.hw_frames_ctx);
+            ffi::av_buffer_unref(&mut self.hw_device_ctx);
+        }
+    }
+}
+
+fn build_x264_encoder(fps: f64) -> Result<ffmpeg::encoder::video::Video> {
     let codec = ffmpeg::encoder::find_by_name("libx264")
         .ok_or(anyhow::anyhow!("libx264 not found"))?;
     let encoder_ctx = ffmpeg::codec::context::Context::new_with_codec(codec);
@@ -222,7 +820,7 @@ fn transcode_to_h264(input_path: &str, output_path: &str, rotation: i32) -> Resu
     encoder.set_bit_rate(2_000_000);
     encoder.set_time_base((1, 1000));
     encoder.set_max_b_frames(0);
-    
+
     let gop_size = fps.round() as u32;
     encoder.set_gop(gop_size);
 
@@ -234,123 +832,248 @@ fn transcode_to_h264(input_path: &str, output_path: &str, rotation: i32) -> Resu
         gop_size, gop_size
     ));
 
-    let mut encoder = encoder.open_as_with(codec, opts)?;
-    let mut ost = octx.add_stream(codec)?;
-    ost.set_parameters(&encoder);
-    octx.write_header()?;
+    Ok(encoder.open_as_with(codec, opts)?)
+}
 
-    let mut decoded_frame = ffmpeg::util::frame::Video::empty();
-    let mut encoded_packet = ffmpeg::Packet::empty();
-    
-    let mut pts_counter = 0;
-    let frame_delay_units = (1000.0 / fps) as i64; 
+fn try_vaapi_encoder(fps: f64, vaapi_device: &str) -> Result<(ffmpeg::encoder::video::Video, VaapiContext)> {
+    let codec = ffmpeg::encoder::find_by_name("h264_vaapi")
+        .ok_or_else(|| anyhow::anyhow!("h264_vaapi encoder not registered"))?;
 
-    let mut to_rgba_scaler: Option<Scaler> = None;
-    let mut to_yuv_scaler: Option<Scaler> = None;
+    let vaapi_ctx = VaapiContext::new(vaapi_device, SCREEN_WIDTH, SCREEN_HEIGHT)?;
 
-    for (stream, pkt) in ictx.packets() {
-        if stream.index() == video_stream_index {
-            decoder.send_packet(&pkt)?;
-            
-            while decoder.receive_frame(&mut decoded_frame).is_ok() {
-                if to_rgba_scaler.is_none() || 
-                   to_rgba_scaler.as_ref().unwrap().input().width != decoded_frame.width() ||
-                   to_rgba_scaler.as_ref().unwrap().input().height != decoded_frame.height() 
-                {
-                    to_rgba_scaler = Some(Scaler::get(
-                        decoded_frame.format(),
-                        decoded_frame.width(),
-                        decoded_frame.height(),
-                        Pixel::RGBA,
-                        decoded_frame.width(),
-                        decoded_frame.height(),
-                        Flags::BILINEAR,
-                    )?);
-                }
+    let encoder_ctx = ffmpeg::codec::context::Context::new_with_codec(codec);
+    let mut encoder = encoder_ctx.encoder().video()?;
 
-                let mut rgba_frame = ffmpeg::util::frame::Video::empty();
-                to_rgba_scaler.as_mut().unwrap().run(&decoded_frame, &mut rgba_frame)?;
-
-                let raw_data = rgba_frame.data(0);
-                let stride = rgba_frame.stride(0);
-                let width = rgba_frame.width();
-                let height = rgba_frame.height();
-                
-                let mut tight_buffer = Vec::with_capacity((width * height * 4) as usize);
-                for y in 0..height as usize {
-                    let start = y * stride;
-                    let end = start + (width as usize * 4);
-                    tight_buffer.extend_from_slice(&raw_data[start..end]);
-                }
+    encoder.set_height(SCREEN_HEIGHT);
+    encoder.set_width(SCREEN_WIDTH);
+    encoder.set_format(Pixel::VAAPI);
+    encoder.set_bit_rate(2_000_000);
+    encoder.set_time_base((1, 1000));
+    encoder.set_max_b_frames(0);
 
-                let img_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> = 
-                    ImageBuffer::from_raw(width, height, tight_buffer)
-                    .ok_or(anyhow::anyhow!("[-] Failed to create image buffer"))?;
-
-                let rotated_buffer = if rotation == 90 {
-                    image::imageops::rotate90(&img_buffer)
-                } else if rotation == -90 || rotation == 270 {
-                    image::imageops::rotate270(&img_buffer)
-                } else if rotation == 180 {
-                    image::imageops::rotate180(&img_buffer)
-                } else {
-                    img_buffer
-                };
-
-                let (rot_w, rot_h) = (rotated_buffer.width(), rotated_buffer.height());
-
-                if to_yuv_scaler.is_none() || 
-                   to_yuv_scaler.as_ref().unwrap().input().width != rot_w ||
-                   to_yuv_scaler.as_ref().unwrap().input().height != rot_h 
-                {
-                     to_yuv_scaler = Some(Scaler::get(
-                        Pixel::RGBA,
-                        rot_w,
-                        rot_h,
-                        Pixel::YUV420P,
-                        SCREEN_WIDTH,
-                        SCREEN_HEIGHT,
-                        Flags::BILINEAR,
-                    )?);
-                }
+    let gop_size = fps.round() as u32;
+    encoder.set_gop(gop_size);
 
-                let mut input_frame_rotated = ffmpeg::util::frame::Video::new(Pixel::RGBA, rot_w, rot_h);
-                let dest_stride = input_frame_rotated.stride(0);
-                let dest_data = input_frame_rotated.data_mut(0);
-                let src_data = rotated_buffer.as_raw();
-                let src_stride = (rot_w * 4) as usize;
-
-                for y in 0..rot_h as usize {
-                    let src_start = y * src_stride;
-                    let src_end = src_start + src_stride;
-                    let dest_start = y * dest_stride;
-                    dest_data[dest_start..dest_start+src_stride].copy_from_slice(&src_data[src_start..src_end]);
-                }
+    unsafe {
+        let raw_ctx = encoder.as_mut_ptr();
+        (*raw_ctx).hw_frames_ctx = ffi::av_buffer_ref(vaapi_ctx.hw_frames_ctx);
+    }
+
+    let mut opts = Dictionary::new();
+    opts.set("rc_mode", "CBR");
+    opts.set("g", &gop_size.to_string());
+    opts.set("keyint_min", &gop_size.to_string());
+
+    let encoder = encoder.open_as_with(codec, opts)?;
+
+    Ok((encoder, vaapi_ctx))
+}
+
+fn try_v4l2m2m_encoder(fps: f64) -> Result<ffmpeg::encoder::video::Video> {
+    let codec = ffmpeg::encoder::find_by_name("h264_v4l2m2m")
+        .ok_or_else(|| anyhow::anyhow!("h264_v4l2m2m encoder not registered"))?;
+
+    let encoder_ctx = ffmpeg::codec::context::Context::new_with_codec(codec);
+    let mut encoder = encoder_ctx.encoder().video()?;
+
+    encoder.set_height(SCREEN_HEIGHT);
+    encoder.set_width(SCREEN_WIDTH);
+    encoder.set_format(Pixel::YUV420P);
+    encoder.set_bit_rate(2_000_000);
+    encoder.set_time_base((1, 1000));
+    encoder.set_max_b_frames(0);
 
-                let mut final_frame = ffmpeg::util::frame::Video::empty();
-                to_yuv_scaler.as_mut().unwrap().run(&input_frame_rotated, &mut final_frame)?;
+    let gop_size = fps.round() as u32;
+    encoder.set_gop(gop_size);
 
-                final_frame.set_pts(Some(pts_counter));
-                pts_counter += frame_delay_units;
+    let mut opts = Dictionary::new();
+    opts.set("num_capture_buffers", "8");
 
-                encoder.send_frame(&final_frame)?;
-                while encoder.receive_packet(&mut encoded_packet).is_ok() {
-                    encoded_packet.set_stream(0);
-                    encoded_packet.write_interleaved(&mut octx)?;
+    Ok(encoder.open_as_with(codec, opts)?)
+}
+
+/// Shared RGBA -> rotate -> YUV420P/NV12 -> H.264 pipeline used by both the
+/// file-based transcode path and the live V4L2 capture path.
+struct FrameTranscoder {
+    encoder: ffmpeg::encoder::video::Video,
+    backend: EncoderBackend,
+    to_rgba_scaler: Option<Scaler>,
+    to_yuv_scaler: Option<Scaler>,
+    pts_counter: i64,
+    frame_delay_units: i64,
+}
+
+impl FrameTranscoder {
+    fn new(fps: f64, choice: EncoderChoice, vaapi_device: &str) -> Result<Self> {
+        let (encoder, backend) = Self::open_encoder(fps, choice, vaapi_device)?;
+
+        Ok(Self {
+            encoder,
+            backend,
+            to_rgba_scaler: None,
+            to_yuv_scaler: None,
+            pts_counter: 0,
+            frame_delay_units: (1000.0 / fps) as i64,
+        })
+    }
+
+    fn open_encoder(
+        fps: f64,
+        choice: EncoderChoice,
+        vaapi_device: &str,
+    ) -> Result<(ffmpeg::encoder::video::Video, EncoderBackend)> {
+        match choice {
+            EncoderChoice::Vaapi => {
+                let (encoder, ctx) = try_vaapi_encoder(fps, vaapi_device)?;
+                Ok((encoder, EncoderBackend::Vaapi(ctx)))
+            }
+            EncoderChoice::V4l2m2m => {
+                Ok((try_v4l2m2m_encoder(fps)?, EncoderBackend::V4l2m2m))
+            }
+            EncoderChoice::X264 => {
+                Ok((build_x264_encoder(fps)?, EncoderBackend::X264))
+            }
+            EncoderChoice::Auto => {
+                match try_vaapi_encoder(fps, vaapi_device) {
+                    Ok((encoder, ctx)) => {
+                        println!("[+] Using hardware encoder: h264_vaapi ({})", vaapi_device);
+                        return Ok((encoder, EncoderBackend::Vaapi(ctx)));
+                    }
+                    Err(e) => println!("[!] h264_vaapi unavailable ({}), trying h264_v4l2m2m...", e),
                 }
+
+                match try_v4l2m2m_encoder(fps) {
+                    Ok(encoder) => {
+                        println!("[+] Using hardware encoder: h264_v4l2m2m");
+                        return Ok((encoder, EncoderBackend::V4l2m2m));
+                    }
+                    Err(e) => println!("[!] h264_v4l2m2m unavailable ({}), falling back to libx264...", e),
+                }
+
+                println!("[+] Using software encoder: libx264");
+                Ok((build_x264_encoder(fps)?, EncoderBackend::X264))
             }
         }
     }
 
-    encoder.send_eof()?;
-    while encoder.receive_packet(&mut encoded_packet).is_ok() {
-        encoded_packet.set_stream(0);
-        encoded_packet.write_interleaved(&mut octx)?;
+    fn encode_frame(
+        &mut self,
+        decoded_frame: &ffmpeg::util::frame::Video,
+        rotation: i32,
+    ) -> Result<Vec<ffmpeg::Packet>> {
+        if self.to_rgba_scaler.is_none() ||
+           self.to_rgba_scaler.as_ref().unwrap().input().width != decoded_frame.width() ||
+           self.to_rgba_scaler.as_ref().unwrap().input().height != decoded_frame.height()
+        {
+            self.to_rgba_scaler = Some(Scaler::get(
+                decoded_frame.format(),
+                decoded_frame.width(),
+                decoded_frame.height(),
+                Pixel::RGBA,
+                decoded_frame.width(),
+                decoded_frame.height(),
+                Flags::BILINEAR,
+            )?);
+        }
+
+        let mut rgba_frame = ffmpeg::util::frame::Video::empty();
+        self.to_rgba_scaler.as_mut().unwrap().run(decoded_frame, &mut rgba_frame)?;
+
+        let raw_data = rgba_frame.data(0);
+        let stride = rgba_frame.stride(0);
+        let width = rgba_frame.width();
+        let height = rgba_frame.height();
+
+        let mut tight_buffer = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height as usize {
+            let start = y * stride;
+            let end = start + (width as usize * 4);
+            tight_buffer.extend_from_slice(&raw_data[start..end]);
+        }
+
+        let img_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
+            ImageBuffer::from_raw(width, height, tight_buffer)
+            .ok_or(anyhow::anyhow!("[-] Failed to create image buffer"))?;
+
+        let rotated_buffer = if rotation == 90 {
+            image::imageops::rotate90(&img_buffer)
+        } else if rotation == -90 || rotation == 270 {
+            image::imageops::rotate270(&img_buffer)
+        } else if rotation == 180 {
+            image::imageops::rotate180(&img_buffer)
+        } else {
+            img_buffer
+        };
+
+        let (rot_w, rot_h) = (rotated_buffer.width(), rotated_buffer.height());
+
+        let sw_format = match self.backend {
+            EncoderBackend::Vaapi(_) => Pixel::NV12,
+            EncoderBackend::X264 | EncoderBackend::V4l2m2m => Pixel::YUV420P,
+        };
+
+        if self.to_yuv_scaler.is_none() ||
+           self.to_yuv_scaler.as_ref().unwrap().input().width != rot_w ||
+           self.to_yuv_scaler.as_ref().unwrap().input().height != rot_h ||
+           self.to_yuv_scaler.as_ref().unwrap().output().format != sw_format
+        {
+            self.to_yuv_scaler = Some(Scaler::get(
+                Pixel::RGBA,
+                rot_w,
+                rot_h,
+                sw_format,
+                SCREEN_WIDTH,
+                SCREEN_HEIGHT,
+                Flags::BILINEAR,
+            )?);
+        }
+
+        let mut input_frame_rotated = ffmpeg::util::frame::Video::new(Pixel::RGBA, rot_w, rot_h);
+        let dest_stride = input_frame_rotated.stride(0);
+        let dest_data = input_frame_rotated.data_mut(0);
+        let src_data = rotated_buffer.as_raw();
+        let src_stride = (rot_w * 4) as usize;
+
+        for y in 0..rot_h as usize {
+            let src_start = y * src_stride;
+            let src_end = src_start + src_stride;
+            let dest_start = y * dest_stride;
+            dest_data[dest_start..dest_start+src_stride].copy_from_slice(&src_data[src_start..src_end]);
+        }
+
+        let mut final_frame = ffmpeg::util::frame::Video::empty();
+        self.to_yuv_scaler.as_mut().unwrap().run(&input_frame_rotated, &mut final_frame)?;
+
+        final_frame.set_pts(Some(self.pts_counter));
+        self.pts_counter += self.frame_delay_units;
+
+        match &self.backend {
+            EncoderBackend::Vaapi(vaapi_ctx) => {
+                let hw_frame = vaapi_ctx.upload(&final_frame)?;
+                self.encoder.send_frame(&hw_frame)?;
+            }
+            EncoderBackend::X264 | EncoderBackend::V4l2m2m => {
+                self.encoder.send_frame(&final_frame)?;
+            }
+        }
+
+        self.drain_encoder()
     }
 
-    octx.write_trailer()?;
+    fn flush(&mut self) -> Result<Vec<ffmpeg::Packet>> {
+        self.encoder.send_eof()?;
+        self.drain_encoder()
+    }
 
-    Ok(fps)
+    fn drain_encoder(&mut self) -> Result<Vec<ffmpeg::Packet>> {
+        let mut packets = Vec::new();
+        let mut encoded_packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut encoded_packet).is_ok() {
+            encoded_packet.set_stream(0);
+            packets.push(encoded_packet.clone());
+        }
+        Ok(packets)
+    }
 }
 
 fn send_packet_to_usb(handle: &mut DeviceHandle<GlobalContext>, frame_data: &[u8]) -> Result<()> {
@@ -405,4 +1128,93 @@ fn open_device(vid: u16, pid: u16) -> Result<DeviceHandle<GlobalContext>> {
         }
     }
     Err(anyhow::anyhow!("[-] Device not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nal(nal_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x00, 0x00, 0x00, 0x01, nal_type];
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn group_access_units_attaches_leading_sei_to_the_following_frame() {
+        // SPS(7), PPS(8), IDR(5), SEI(6), P(1)
+        let mut stream = Vec::new();
+        stream.extend(nal(7, &[0xAA]));
+        stream.extend(nal(8, &[0xBB]));
+        stream.extend(nal(5, &[0xCC]));
+        stream.extend(nal(6, &[0xDD]));
+        stream.extend(nal(1, &[0xEE]));
+
+        let nals = scan_annexb_nals(&stream);
+        let access_units = group_access_units(&nals);
+
+        assert_eq!(access_units.len(), 2);
+        assert_eq!(access_units[0], nal(7, &[0xAA]).into_iter()
+            .chain(nal(8, &[0xBB]))
+            .chain(nal(5, &[0xCC]))
+            .collect::<Vec<u8>>());
+        assert_eq!(access_units[1], nal(6, &[0xDD]).into_iter()
+            .chain(nal(1, &[0xEE]))
+            .collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn scan_annexb_nals_reads_type_and_both_start_code_lengths() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&[0x00, 0x00, 0x01, 0x67, 0x01]); // 3-byte start code, SPS
+        stream.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x65, 0x02]); // 4-byte start code, IDR
+
+        let nals = scan_annexb_nals(&stream);
+
+        assert_eq!(nals.len(), 2);
+        assert_eq!(nals[0].nal_type, 7);
+        assert_eq!(nals[0].bytes, &[0x00, 0x00, 0x01, 0x67, 0x01]);
+        assert_eq!(nals[1].nal_type, 5);
+        assert_eq!(nals[1].bytes, &[0x00, 0x00, 0x00, 0x01, 0x65, 0x02]);
+    }
+
+    #[test]
+    fn packet_ring_blocks_when_full_until_consumer_drains() {
+        let ring = Arc::new(PacketRing::new(2, false));
+        ring.enqueue(vec![1]);
+        ring.enqueue(vec![2]);
+
+        let producer_ring = ring.clone();
+        let enqueued_third = Arc::new(AtomicBool::new(false));
+        let flag = enqueued_third.clone();
+        let producer = std::thread::spawn(move || {
+            producer_ring.enqueue(vec![3]);
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!enqueued_third.load(Ordering::SeqCst), "enqueue should block while the ring is full");
+
+        assert_eq!(ring.dequeue(), Some(vec![1]));
+        producer.join().unwrap();
+        assert!(enqueued_third.load(Ordering::SeqCst), "enqueue should have unblocked once a slot freed up");
+
+        assert_eq!(ring.dequeue(), Some(vec![2]));
+        assert_eq!(ring.dequeue(), Some(vec![3]));
+    }
+
+    #[test]
+    fn packet_ring_drops_oldest_when_full_in_drop_mode() {
+        let ring = PacketRing::new(4, true);
+        for i in 0..6u8 {
+            ring.enqueue(vec![i]);
+        }
+
+        // The first two packets (0, 1) should have been evicted to make
+        // room, leaving only the most recent `capacity` packets queued.
+        assert_eq!(ring.dequeue(), Some(vec![2]));
+        assert_eq!(ring.dequeue(), Some(vec![3]));
+        assert_eq!(ring.dequeue(), Some(vec![4]));
+        assert_eq!(ring.dequeue(), Some(vec![5]));
+    }
 }
\ No newline at end of file